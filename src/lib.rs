@@ -10,13 +10,173 @@ extern crate block_allocator;
 use std::sync::atomic::{self, AtomicUsize, Ordering};
 use std::ops::Deref;
 use std::io::Read;
-use std::{io, mem, fmt};
+use std::mem::MaybeUninit;
+use std::{io, mem, ptr, cmp, fmt, slice};
 use block_allocator::Allocator;
 
+/// The error returned when the backing `Allocator` has no free block.
+///
+/// This is the fallible counterpart to the panic that `AppendBuf::new`
+/// raises on exhaustion; servers under memory pressure can match on it to
+/// shed load rather than crash.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct AllocError;
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the allocator has no free block available")
+    }
+}
+
+impl ::std::error::Error for AllocError {
+    fn description(&self) -> &str {
+        "the allocator has no free block available"
+    }
+}
+
+/// A write-only view over possibly-uninitialized buffer space.
+///
+/// Handed out by `AppendBuf::get_write_buf`, this wraps the unwritten tail
+/// of an allocator block, which may contain uninitialized bytes. It only
+/// exposes operations that *write* into that region, so callers can never
+/// read the uninitialized memory through it (which would be undefined
+/// behaviour). It is the local analogue of the `bytes` crate's
+/// `UninitSlice`.
+#[repr(transparent)]
+pub struct UninitBuf {
+    data: [MaybeUninit<u8>]
+}
+
+impl UninitBuf {
+    /// Reinterpret a mutable byte slice as an `UninitBuf`.
+    ///
+    /// Unsafe because the caller promises the resulting handle is only used
+    /// to write the region, never to read bytes that were never written.
+    unsafe fn from_bytes_mut(bytes: &mut [u8]) -> &mut UninitBuf {
+        // `#[repr(transparent)]` guarantees `UninitBuf` and
+        // `[MaybeUninit<u8>]` share a layout, and `MaybeUninit<u8>` shares
+        // one with `u8`, so this cast only relabels the element type.
+        let ptr = bytes.as_mut_ptr() as *mut MaybeUninit<u8>;
+        &mut *(ptr::slice_from_raw_parts_mut(ptr, bytes.len()) as *mut UninitBuf)
+    }
+
+    /// The number of bytes this region can hold.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// A raw pointer to the start of the region.
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.data.as_mut_ptr() as *mut u8
+    }
+
+    /// Copy up to `len()` bytes from `src` into the region.
+    ///
+    /// Returns the number of bytes written, which is `min(self.len(),
+    /// src.len())`. Implemented with `ptr::copy_nonoverlapping` so no read
+    /// of the uninitialized destination ever occurs.
+    pub fn write(&mut self, src: &[u8]) -> usize {
+        let amount = cmp::min(self.len(), src.len());
+        unsafe {
+            ptr::copy_nonoverlapping(src.as_ptr(), self.as_mut_ptr(), amount);
+        }
+        amount
+    }
+
+    /// Zero the whole region and return it as an initialized `&mut [u8]`.
+    ///
+    /// Once zeroed the memory is initialized, so handing the slice to code
+    /// that reads before writing (for example an untrusted `io::Read`) is
+    /// sound.
+    pub fn zeroed(&mut self) -> &mut [u8] {
+        let len = self.len();
+        unsafe {
+            ptr::write_bytes(self.as_mut_ptr(), 0, len);
+            slice::from_raw_parts_mut(self.as_mut_ptr(), len)
+        }
+    }
+}
+
+/// An incremental Fletcher-64 checksum.
+///
+/// Modelled on the scheme Fuchsia's fxfs uses for extents: the input is
+/// consumed as little-endian 32-bit words, with `lo` and `hi` accumulated
+/// modulo `2^32 - 1`. Appends need not be word-aligned; a short tail is
+/// stashed until the next append completes the word, and only zero-padded
+/// when the checksum is finalized in `finish`.
+#[derive(Clone)]
+struct Fletcher64 {
+    lo: u64,
+    hi: u64,
+    rem: [u8; 4],
+    rem_len: usize
+}
+
+impl Fletcher64 {
+    const MODULUS: u64 = (1u64 << 32) - 1;
+
+    fn new() -> Fletcher64 {
+        Fletcher64 { lo: 0, hi: 0, rem: [0; 4], rem_len: 0 }
+    }
+
+    /// Fold a complete little-endian word into the accumulators.
+    fn step(&mut self, word: u32) {
+        self.lo = (self.lo + word as u64) % Fletcher64::MODULUS;
+        self.hi = (self.hi + self.lo) % Fletcher64::MODULUS;
+    }
+
+    /// Incorporate more bytes of the stream.
+    fn update(&mut self, mut data: &[u8]) {
+        // Finish any word left partially filled by the previous append.
+        while self.rem_len > 0 && !data.is_empty() {
+            self.rem[self.rem_len] = data[0];
+            self.rem_len += 1;
+            data = &data[1..];
+            if self.rem_len == 4 {
+                let word = u32::from_le_bytes(self.rem);
+                self.step(word);
+                self.rem_len = 0;
+            }
+        }
+
+        while data.len() >= 4 {
+            let word = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+            self.step(word);
+            data = &data[4..];
+        }
+
+        // Stash the sub-word tail for the next append to complete.
+        for &byte in data {
+            self.rem[self.rem_len] = byte;
+            self.rem_len += 1;
+        }
+    }
+
+    /// The checksum over everything seen so far, zero-padding any tail.
+    fn finish(&self) -> u64 {
+        let mut copy = self.clone();
+        if copy.rem_len > 0 {
+            for byte in &mut copy.rem[copy.rem_len..] { *byte = 0; }
+            let word = u32::from_le_bytes(copy.rem);
+            copy.step(word);
+        }
+        (copy.hi << 32) | copy.lo
+    }
+
+    /// The checksum of a standalone byte range.
+    fn of(data: &[u8]) -> u64 {
+        let mut state = Fletcher64::new();
+        state.update(data);
+        state.finish()
+    }
+}
+
 /// An append-only, atomically reference counted buffer.
 pub struct AppendBuf<'a> {
     alloc: *mut AllocInfo<'a>,
-    position: usize
+    position: usize,
+    zeroed_upto: usize,
+    checksum: Fletcher64
 }
 
 unsafe impl<'a> Send for AppendBuf<'a> {}
@@ -35,7 +195,8 @@ unsafe impl<'a> Sync for AllocInfo<'a> {}
 pub struct Slice<'a> {
     alloc: *mut AllocInfo<'a>,
     offset: usize,
-    len: usize
+    len: usize,
+    read_pos: usize
 }
 
 unsafe impl<'a> Send for Slice<'a> {}
@@ -55,7 +216,8 @@ impl<'a> Slice<'a> {
         Slice {
             alloc: self.alloc,
             offset: self.offset + offset,
-            len: self.len - offset
+            len: self.len - offset,
+            read_pos: 0
         }
     }
 
@@ -72,7 +234,8 @@ impl<'a> Slice<'a> {
         Slice {
             alloc: self.alloc,
             offset: self.offset,
-            len: len
+            len: len,
+            read_pos: 0
         }
     }
 
@@ -83,6 +246,103 @@ impl<'a> Slice<'a> {
         slice.slice_to(end - start)
     }
 
+    /// The Fletcher-64 checksum over the bytes this view covers.
+    ///
+    /// Recomputed from the view's range, so a `Slice` peeled off an
+    /// `AppendBuf` validates exactly the bytes it spans — matching
+    /// `AppendBuf::checksum` when the view covers the whole buffer.
+    pub fn checksum(&self) -> u64 {
+        Fletcher64::of(self)
+    }
+
+    /// Split the slice in two at the given index.
+    ///
+    /// Returns a new `Slice` covering `[0, at)` while `self` is mutated to
+    /// cover `[at, len)`. The returned half bumps the `AllocInfo` refcount,
+    /// so each half keeps the block alive independently. Panics if
+    /// `at > len`.
+    pub fn split_to(&mut self, at: usize) -> Slice<'a> {
+        if self.len < at {
+            panic!("split_to past the end of an appendbuf::Slice<'a>,
+                   the length was {:?} and the desired split was {:?}",
+                   self.len, at);
+        }
+
+        self.allocinfo().increment();
+
+        let head = Slice {
+            alloc: self.alloc,
+            offset: self.offset,
+            len: at,
+            read_pos: ::std::cmp::min(self.read_pos, at)
+        };
+
+        self.offset += at;
+        self.len -= at;
+        self.read_pos -= head.read_pos;
+
+        head
+    }
+
+    /// Split the slice in two at the given index.
+    ///
+    /// The mirror of `split_to`: returns a new `Slice` covering `[at, len)`
+    /// while `self` is mutated to cover `[0, at)`. The returned half bumps
+    /// the `AllocInfo` refcount. Panics if `at > len`.
+    pub fn split_off(&mut self, at: usize) -> Slice<'a> {
+        if self.len < at {
+            panic!("split_off past the end of an appendbuf::Slice<'a>,
+                   the length was {:?} and the desired split was {:?}",
+                   self.len, at);
+        }
+
+        self.allocinfo().increment();
+
+        let tail = Slice {
+            alloc: self.alloc,
+            offset: self.offset + at,
+            len: self.len - at,
+            read_pos: self.read_pos.saturating_sub(at)
+        };
+
+        self.len = at;
+        self.read_pos = ::std::cmp::min(self.read_pos, at);
+
+        tail
+    }
+
+    /// The number of bytes between the cursor and the end of the view.
+    ///
+    /// This mirrors `bytes::Buf::remaining`; it shrinks as `advance` is
+    /// called and is unaffected by the underlying buffer's capacity.
+    pub fn remaining(&self) -> usize {
+        self.len - self.read_pos
+    }
+
+    /// The bytes from the current cursor to the end of the view.
+    ///
+    /// Equivalent to `bytes::Buf::chunk`. Because a `Slice` is always a
+    /// single contiguous block this returns every remaining byte in one
+    /// shot; it never returns an empty slice unless the cursor has reached
+    /// the end.
+    pub fn chunk(&self) -> &[u8] {
+        &(**self)[self.read_pos..]
+    }
+
+    /// Advance the internal read cursor by `cnt` bytes.
+    ///
+    /// Like `bytes::Buf::advance`, this panics if `cnt` is greater than
+    /// `remaining`.
+    pub fn advance(&mut self, cnt: usize) {
+        if cnt > self.remaining() {
+            panic!("advanced past the end of an appendbuf::Slice<'a>,
+                   the remaining length was {:?} and the desired advance was {:?}",
+                   self.remaining(), cnt);
+        }
+
+        self.read_pos += cnt;
+    }
+
     fn allocinfo(&self) -> &AllocInfo {
         unsafe { mem::transmute(self.alloc) }
     }
@@ -90,11 +350,24 @@ impl<'a> Slice<'a> {
 
 impl<'a> AppendBuf<'a> {
     /// Create a new, empty AppendBuf<'a> with the given capacity.
+    ///
+    /// Panics if the allocator is exhausted; use `try_new` to handle that
+    /// case without unwinding.
     pub fn new(allocator: &'a Allocator) -> AppendBuf<'a> {
-        AppendBuf {
-            alloc: unsafe { AllocInfo::allocate(allocator) },
-            position: 0
-        }
+        AppendBuf::try_new(allocator).unwrap()
+    }
+
+    /// Try to create a new, empty AppendBuf<'a> with the given capacity.
+    ///
+    /// Returns `Err(AllocError)` when the allocator has no free block
+    /// instead of panicking the way `new` does.
+    pub fn try_new(allocator: &'a Allocator) -> Result<AppendBuf<'a>, AllocError> {
+        Ok(AppendBuf {
+            alloc: unsafe { AllocInfo::try_allocate(allocator)? },
+            position: 0,
+            zeroed_upto: 0,
+            checksum: Fletcher64::new()
+        })
     }
 
     /// Create a new Slice<'a> of the entire AppendBuf<'a> so far.
@@ -104,10 +377,19 @@ impl<'a> AppendBuf<'a> {
         Slice {
             alloc: self.alloc,
             offset: 0,
-            len: self.position
+            len: self.position,
+            read_pos: 0
         }
     }
 
+    /// The Fletcher-64 checksum over every byte appended so far.
+    ///
+    /// Maintained incrementally inside `fill`/`advance`, so reading it is
+    /// cheap and needs no rescan of the buffer.
+    pub fn checksum(&self) -> u64 {
+        self.checksum.finish()
+    }
+
     /// Retrieve the amount of remaining space in the AppendBuf<'a>.
     pub fn remaining(&self) -> usize {
         self.allocinfo().buf.len() - self.position
@@ -118,42 +400,97 @@ impl<'a> AppendBuf<'a> {
     /// This is an alternative to using the implementation of `std::io::Write`
     /// which does not unnecessarily use `Result`.
     pub fn fill(&mut self, buf: &[u8]) -> usize {
-        use std::io::Write;
-
-        // FIXME: Use std::slice::bytes::copy_memory when it is stabilized.
-        let amount = self.get_write_buf().write(buf).unwrap();
+        let amount = self.get_write_buf().write(buf);
+        self.checksum.update(&buf[..amount]);
         self.position += amount;
 
         amount
     }
 
+    /// Write the data in the passed buffer, reporting whether the buffer is full.
+    ///
+    /// Like `fill` this writes as much of `buf` as will fit in the single
+    /// backing block, but it also returns whether there is any space left:
+    /// the tuple is `(bytes_written, full)`, where `full` is `true` once
+    /// `remaining` has reached zero. A caller seeing `full` (especially with
+    /// `bytes_written < buf.len()`) knows the block could not absorb the
+    /// whole write and can allocate a fresh buffer or shed load.
+    pub fn try_fill(&mut self, buf: &[u8]) -> (usize, bool) {
+        let amount = self.fill(buf);
+        (amount, self.remaining() == 0)
+    }
+
     /// Get the remaining space in the AppendBuf<'a> for writing.
     ///
-    /// If you wish the see the data written in subsequent Slice<'a>s,
-    /// you must also call `advance` with the amount written.
+    /// The returned `UninitBuf` wraps possibly-uninitialized allocator
+    /// memory and so only exposes write operations; there is no readable
+    /// `&[u8]` to accidentally observe that memory. If you wish to see the
+    /// data written in subsequent Slice<'a>s, you must also call `advance`
+    /// with the amount written.
+    pub fn get_write_buf(&mut self) -> &mut UninitBuf {
+        let position = self.position;
+        unsafe { UninitBuf::from_bytes_mut(&mut self.allocinfo_mut().buf[position..]) }
+    }
+
+    /// Get the remaining write space as an initialized `&mut [u8]`.
+    ///
+    /// Unlike `get_write_buf` this initializes the region first, so it is
+    /// safe to hand to an untrusted `io::Read` (as `read_from` does) that
+    /// might misreport how many bytes it wrote.
     ///
-    /// Reads from this buffer are reads into uninitalized memory,
-    /// and so should be carefully avoided.
-    pub fn get_write_buf(&mut self) -> &mut [u8] {
+    /// Only the bytes not already zeroed by a previous call are touched: a
+    /// high-water mark tracks how far the block has been initialized, so
+    /// repeated small reads into a large block cost O(capacity) in total
+    /// rather than O(capacity) per call. Bytes below the mark that were
+    /// written (through this slice or `get_write_buf`) without a matching
+    /// `advance` keep the values written there; they are not zeroed again.
+    pub fn zeroed_write_buf(&mut self) -> &mut [u8] {
         let position = self.position;
-         &mut self.allocinfo_mut().buf[position..]
+        let capacity = self.allocinfo().buf.len();
+
+        // Everything in `[position, zeroed_upto)` was zeroed by an earlier
+        // call, so it is initialized, though it may hold bytes written there
+        // without a matching `advance`. Only the tail past the high-water
+        // mark needs initializing.
+        let start = cmp::max(position, self.zeroed_upto);
+        if capacity > start {
+            unsafe {
+                UninitBuf::from_bytes_mut(&mut self.allocinfo_mut().buf[start..]).zeroed();
+            }
+            self.zeroed_upto = capacity;
+        }
+
+        &mut self.allocinfo_mut().buf[position..]
     }
 
     /// Advance the position of the AppendBuf<'a>.
     ///
     /// You should only advance the buffer if you have written to a
-    /// buffer returned by `get_write_buf`.
+    /// buffer returned by `get_write_buf`. Advancing past the end of the
+    /// backing block — i.e. further than the region `get_write_buf`
+    /// handed out — is a bug and is caught by a `debug_assert`.
     pub unsafe fn advance(&mut self, amount: usize) {
-         self.position += amount;
+        debug_assert!(self.position + amount <= self.allocinfo().buf.len(),
+            "advanced an AppendBuf past the end of its backing block");
+
+        // Fold the freshly written bytes into the rolling checksum. We read
+        // them through a raw pointer to avoid aliasing the `&mut self`
+        // borrow held by `self.checksum`.
+        let start = self.position;
+        let written = slice::from_raw_parts((*self.alloc).buf.as_ptr().add(start), amount);
+        self.checksum.update(written);
+
+        self.position += amount;
     }
 
     /// Read from the given io::Read into the AppendBuf<'a>.
     ///
-    /// Safety note: it is possible to read uninitalized memory if the
-    /// passed io::Read incorrectly reports the number of bytes written to
-    /// buffers passed to it.
+    /// The read target is initialized first (see `zeroed_write_buf`), so a
+    /// misbehaving `io::Read` that overreports its byte count can only
+    /// expose zeros or bytes written there earlier, never uninitialized
+    /// memory.
     pub fn read_from<R: Read>(&mut self, reader: &mut R) -> io::Result<usize> {
-        reader.read(self.get_write_buf()).map(|n| {
+        reader.read(self.zeroed_write_buf()).map(|n| {
             unsafe { self.advance(n) };
             n
         })
@@ -168,6 +505,199 @@ impl<'a> AppendBuf<'a> {
     }
 }
 
+/// An append-only buffer that spans more than one `Allocator` block.
+///
+/// Unlike `AppendBuf`, which is capped at a single block and silently
+/// truncates once full, a `ChainBuf` transparently allocates a fresh
+/// `AllocInfo` block whenever the current one fills and links the blocks
+/// into a list. Writers get an unbounded append target while readers keep
+/// zero-copy `Slice` views into the individual blocks.
+pub struct ChainBuf<'a> {
+    allocator: &'a Allocator<'a>,
+    blocks: Vec<ChainBlock<'a>>,
+    read_block: usize,
+    read_pos: usize
+}
+
+unsafe impl<'a> Send for ChainBuf<'a> {}
+unsafe impl<'a> Sync for ChainBuf<'a> {}
+
+struct ChainBlock<'a> {
+    alloc: *mut AllocInfo<'a>,
+    filled: usize
+}
+
+impl<'a> ChainBuf<'a> {
+    /// Create a new, empty ChainBuf<'a> backed by the given allocator.
+    ///
+    /// Panics if the allocator is exhausted; use `try_new` to handle that
+    /// case without unwinding.
+    pub fn new(allocator: &'a Allocator) -> ChainBuf<'a> {
+        ChainBuf::try_new(allocator).unwrap()
+    }
+
+    /// Try to create a new, empty ChainBuf<'a> backed by the given allocator.
+    ///
+    /// Returns `Err(AllocError)` when the allocator has no free block
+    /// instead of panicking the way `new` does.
+    pub fn try_new(allocator: &'a Allocator) -> Result<ChainBuf<'a>, AllocError> {
+        let block = ChainBlock {
+            alloc: unsafe { AllocInfo::try_allocate(allocator)? },
+            filled: 0
+        };
+
+        Ok(ChainBuf {
+            allocator: allocator,
+            blocks: vec![block],
+            read_block: 0,
+            read_pos: 0
+        })
+    }
+
+    /// The total number of bytes appended so far across every block.
+    pub fn len(&self) -> usize {
+        self.blocks.iter().map(|block| block.filled).sum()
+    }
+
+    /// Write the data in the passed buffer onto the ChainBuf<'a>.
+    ///
+    /// Fresh blocks are allocated as needed, so the entire buffer is always
+    /// written; the returned count equals `buf.len()`. Panics if the
+    /// allocator is exhausted mid-write; use `try_fill` to handle that case.
+    pub fn fill(&mut self, buf: &[u8]) -> usize {
+        self.try_fill(buf).unwrap()
+    }
+
+    /// Write the data in the passed buffer, allocating fresh blocks as needed.
+    ///
+    /// Returns the number of bytes written, which equals `buf.len()` on
+    /// success. Returns `Err(AllocError)` if a fresh block is needed but the
+    /// allocator is exhausted; bytes that fit in the already-allocated
+    /// blocks have still been written in that case.
+    pub fn try_fill(&mut self, buf: &[u8]) -> Result<usize, AllocError> {
+        let mut written = 0;
+
+        while written < buf.len() {
+            if self.blocks.last().map_or(true, |block| block.filled == block.capacity()) {
+                self.blocks.push(ChainBlock {
+                    alloc: unsafe { AllocInfo::try_allocate(self.allocator)? },
+                    filled: 0
+                });
+            }
+
+            let block = self.blocks.last_mut().unwrap();
+            let filled = block.filled;
+            let mut dst = &mut block.buf_mut()[filled..];
+            let amount = {
+                use std::io::Write;
+                dst.write(&buf[written..]).unwrap()
+            };
+            block.filled += amount;
+            written += amount;
+        }
+
+        Ok(written)
+    }
+
+    /// The number of unread bytes between the read cursor and the end.
+    pub fn remaining(&self) -> usize {
+        if self.read_block >= self.blocks.len() { return 0 }
+
+        let current = self.blocks[self.read_block].filled - self.read_pos;
+        let rest: usize = self.blocks[self.read_block + 1..]
+            .iter().map(|block| block.filled).sum();
+        current + rest
+    }
+
+    /// The bytes of the current contiguous segment from the read cursor.
+    ///
+    /// Like `bytes::Buf::chunk` this yields a single block's worth of data;
+    /// call `advance` to cross a segment boundary and expose the next block.
+    pub fn chunk(&self) -> &[u8] {
+        if self.read_block >= self.blocks.len() { return &[] }
+
+        let (mut index, mut pos) = (self.read_block, self.read_pos);
+        // A cursor left at the end of a block that has since been followed
+        // by a fresh one reads from the start of the next block.
+        if pos == self.blocks[index].filled && index + 1 < self.blocks.len() {
+            index += 1;
+            pos = 0;
+        }
+
+        let block = &self.blocks[index];
+        &block.buf()[pos..block.filled]
+    }
+
+    /// Advance the read cursor by `cnt` bytes, crossing block boundaries.
+    ///
+    /// Panics if `cnt` is greater than `remaining`.
+    pub fn advance(&mut self, mut cnt: usize) {
+        if cnt > self.remaining() {
+            panic!("advanced past the end of an appendbuf::ChainBuf<'a>,
+                   the remaining length was {:?} and the desired advance was {:?}",
+                   self.remaining(), cnt);
+        }
+
+        while cnt > 0 {
+            let available = self.blocks[self.read_block].filled - self.read_pos;
+            // The cursor stays inside the last block, so bytes filled into
+            // it later are still counted by `remaining`.
+            if cnt < available || self.read_block + 1 == self.blocks.len() {
+                self.read_pos += cnt;
+                break;
+            }
+
+            cnt -= available;
+            self.read_block += 1;
+            self.read_pos = 0;
+        }
+    }
+
+    /// Yield a per-block `Slice` over every block's written bytes.
+    ///
+    /// Each `Slice` bumps the refcount of its own block, so the returned
+    /// views keep exactly the blocks they cover alive and may outlive the
+    /// `ChainBuf`.
+    pub fn slices(&self) -> impl Iterator<Item = Slice<'a>> {
+        self.blocks.iter().map(|block| {
+            block.allocinfo().increment();
+
+            Slice {
+                alloc: block.alloc,
+                offset: 0,
+                len: block.filled,
+                read_pos: 0
+            }
+        }).collect::<Vec<_>>().into_iter()
+    }
+}
+
+impl<'a> ChainBlock<'a> {
+    fn allocinfo(&self) -> &AllocInfo {
+        unsafe { mem::transmute(self.alloc) }
+    }
+
+    fn capacity(&self) -> usize {
+        self.allocinfo().buf.len()
+    }
+
+    fn buf(&self) -> &[u8] {
+        unsafe { &(*self.alloc).buf }
+    }
+
+    fn buf_mut(&mut self) -> &mut [u8] {
+        unsafe { &mut (*self.alloc).buf }
+    }
+}
+
+impl<'a> Drop for ChainBuf<'a> {
+    fn drop(&mut self) {
+        for block in &self.blocks {
+            unsafe { (*block.alloc).decrement() }
+        }
+    }
+}
+
 impl<'a> fmt::Debug for AppendBuf<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Debug::fmt(&**self, f)
@@ -219,21 +749,29 @@ impl<'a> Clone for Slice<'a> {
         Slice {
             alloc: self.alloc,
             offset: self.offset,
-            len: self.len
+            len: self.len,
+            read_pos: self.read_pos
         }
     }
 }
 
 impl<'a> AllocInfo<'a> {
-    unsafe fn allocate(allocator : &'a Allocator) -> *mut Self {
-        //TODO Handle this error
-        let buf = allocator.alloc_raw().unwrap();
+    unsafe fn try_allocate(allocator : &'a Allocator) -> Result<*mut Self, AllocError> {
+        let buf = match allocator.alloc_raw() {
+            Some(buf) => buf,
+            None => return Err(AllocError)
+        };
         let raw_size = allocator.get_block_size() as usize;
         let usable_size = raw_size - (mem::size_of::<AtomicUsize>() + mem::size_of::<&Allocator>());
-        let this = mem::transmute::<_, *mut Self>((buf, usable_size));
+        // Synthesize the unsized `AllocInfo` pointer directly from the block
+        // pointer and the length of its trailing `buf: [u8]` field, rather
+        // than relying on undocumented fat-pointer layout via `transmute`.
+        // The slice length becomes the DST metadata, so the raw block
+        // pointer keeps its provenance over the whole allocation.
+        let this = ptr::slice_from_raw_parts_mut(buf, usable_size) as *mut Self;
         (*this).refcount = AtomicUsize::new(1);
         (*this).allocator = allocator;
-        this
+        Ok(this)
     }
 
     #[inline(always)]
@@ -268,9 +806,16 @@ impl<'a> AllocInfo<'a> {
         // [1]: (www.boost.org/doc/libs/1_55_0/doc/html/atomic/usage_examples.html)
         atomic::fence(Ordering::Acquire);
 
-        let alloc  = self.allocator;
-        let (ptr, _) : (*mut u8, usize) = mem::transmute(self);
-        alloc.free_raw(mem::transmute(ptr)).unwrap(); // TODO handle this result better
+        let alloc = self.allocator;
+        // The struct is laid out at the start of the block, so casting the
+        // fat `&Self` to a thin `*mut u8` recovers the original block
+        // pointer (with its full-allocation provenance) without tearing a
+        // tuple out of the fat pointer's private layout.
+        let ptr = self as *const Self as *mut u8;
+        // This runs from a `Drop`, which cannot propagate an error and must
+        // not unwind, so a failure to return the block to the allocator is
+        // swallowed rather than panicking (which would abort during unwind).
+        let _ = alloc.free_raw(ptr);
     }
 }
 
@@ -312,6 +857,66 @@ fn test_overlong_write() {
     assert_eq!(&*slice, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]);
 }
 
+#[test]
+fn test_chain_spans_blocks() {
+    // A 32-byte block leaves 16 usable bytes after the header, so 40 bytes
+    // of payload must span three blocks.
+    let alloc = Allocator::new(32, 10).unwrap();
+    let data: Vec<u8> = (0..40u8).collect();
+
+    let mut chain = ChainBuf::new(&alloc);
+    assert_eq!(chain.fill(&data), 40);
+    assert_eq!(chain.len(), 40);
+
+    // The per-block slices, concatenated, reconstruct the full stream.
+    let mut rebuilt = Vec::new();
+    for slice in chain.slices() {
+        rebuilt.extend_from_slice(&slice);
+    }
+    assert_eq!(rebuilt, data);
+
+    // The cursor API walks across segment boundaries.
+    assert_eq!(chain.remaining(), 40);
+    let mut seen = Vec::new();
+    while chain.remaining() > 0 {
+        let chunk = chain.chunk().to_vec();
+        assert!(!chunk.is_empty());
+        seen.extend_from_slice(&chunk);
+        let n = chunk.len();
+        chain.advance(n);
+    }
+    assert_eq!(seen, data);
+}
+
+#[test]
+fn test_try_new_reports_exhaustion() {
+    let alloc = Allocator::new(32, 1).unwrap();
+    let buf = AppendBuf::try_new(&alloc).unwrap();
+
+    // The single block is taken, so a second attempt fails rather than panics.
+    assert!(AppendBuf::try_new(&alloc).is_err());
+
+    drop(buf);
+    // Once the block is freed it can be handed out again.
+    assert!(AppendBuf::try_new(&alloc).is_ok());
+}
+
+#[test]
+fn test_try_fill_signals_full() {
+    // 32-byte block leaves 16 usable bytes.
+    let alloc = Allocator::new(32, 10).unwrap();
+    let mut buf = AppendBuf::new(&alloc);
+
+    let data: Vec<u8> = (0..20u8).collect();
+    let (written, full) = buf.try_fill(&data);
+    assert_eq!(written, 16);
+    assert!(full);
+
+    let (written, full) = buf.try_fill(&[1, 2, 3]);
+    assert_eq!(written, 0);
+    assert!(full);
+}
+
 #[test]
 fn test_slice_slicing() {
     let alloc = Allocator::new(32, 10).unwrap();
@@ -326,6 +931,128 @@ fn test_slice_slicing() {
     assert_eq!(&*buf.slice().slice(2, 7), &data[2..7]);
 }
 
+#[test]
+fn test_slice_buf_cursor() {
+    let alloc = Allocator::new(32, 10).unwrap();
+    let data = &[1, 2, 3, 4, 5, 6, 7, 8];
+
+    let mut buf = AppendBuf::new(&alloc);
+    assert_eq!(buf.fill(data), 8);
+
+    let mut slice = buf.slice();
+    assert_eq!(slice.remaining(), 8);
+    assert_eq!(slice.chunk(), data);
+
+    slice.advance(3);
+    assert_eq!(slice.remaining(), 5);
+    assert_eq!(slice.chunk(), &data[3..]);
+
+    slice.advance(5);
+    assert_eq!(slice.remaining(), 0);
+    assert_eq!(slice.chunk(), &[]);
+}
+
+#[test]
+#[should_panic = "the desired advance"]
+fn test_slice_advance_bounds_checks() {
+    let alloc = Allocator::new(32, 10).unwrap();
+    let mut buf = AppendBuf::new(&alloc);
+    assert_eq!(buf.fill(&[1, 2, 3, 4]), 4);
+
+    buf.slice().advance(100);
+}
+
+#[test]
+fn test_slice_split() {
+    let alloc = Allocator::new(32, 10).unwrap();
+    let data = &[1, 2, 3, 4, 5, 6, 7, 8];
+
+    let mut buf = AppendBuf::new(&alloc);
+    assert_eq!(buf.fill(data), 8);
+
+    let mut slice = buf.slice();
+    let head = slice.split_to(3);
+    assert_eq!(&*head, &data[..3]);
+    assert_eq!(&*slice, &data[3..]);
+
+    let tail = slice.split_off(2);
+    assert_eq!(&*slice, &data[3..5]);
+    assert_eq!(&*tail, &data[5..]);
+
+    // Each half keeps the block alive on its own.
+    drop(slice);
+    assert_eq!(&*head, &data[..3]);
+    assert_eq!(&*tail, &data[5..]);
+}
+
+#[test]
+#[should_panic = "the desired split"]
+fn test_split_to_bounds_checks() {
+    let alloc = Allocator::new(32, 10).unwrap();
+    let mut buf = AppendBuf::new(&alloc);
+    assert_eq!(buf.fill(&[1, 2, 3, 4]), 4);
+
+    buf.slice().split_to(100);
+}
+
+#[test]
+fn test_write_buf_and_advance() {
+    let alloc = Allocator::new(128, 10).unwrap();
+    let mut buf = AppendBuf::new(&alloc);
+
+    {
+        let write_buf = buf.get_write_buf();
+        assert!(write_buf.len() >= 4);
+        assert_eq!(write_buf.write(&[9, 8, 7, 6]), 4);
+    }
+    unsafe { buf.advance(4) };
+
+    assert_eq!(&*buf, &[9, 8, 7, 6]);
+}
+
+#[test]
+fn test_read_from_initializes() {
+    let alloc = Allocator::new(128, 10).unwrap();
+    let mut buf = AppendBuf::new(&alloc);
+
+    let data = [1u8, 2, 3, 4, 5];
+    let mut reader = &data[..];
+    assert_eq!(buf.read_from(&mut reader).unwrap(), 5);
+    assert_eq!(&*buf, &data);
+
+    // The rest of the region handed to the reader was zeroed beforehand.
+    assert!(buf.zeroed_write_buf().iter().all(|&b| b == 0));
+}
+
+#[test]
+fn test_checksum_incremental_matches_oneshot() {
+    let alloc = Allocator::new(128, 10).unwrap();
+    let data: Vec<u8> = (0..23u8).collect();
+
+    // Appending in awkward, non-word-aligned chunks must match a single
+    // append of the same bytes.
+    let mut chunked = AppendBuf::new(&alloc);
+    chunked.fill(&data[..3]);
+    chunked.fill(&data[3..10]);
+    chunked.fill(&data[10..]);
+
+    let mut whole = AppendBuf::new(&alloc);
+    whole.fill(&data);
+
+    assert_eq!(chunked.checksum(), whole.checksum());
+
+    // A full-buffer slice agrees with the buffer, and a sub-slice matches a
+    // standalone computation over the same bytes.
+    assert_eq!(whole.slice().checksum(), whole.checksum());
+    let sub = whole.slice().slice(4, 19);
+    let expected = {
+        let mut state = Fletcher64::new();
+        state.update(&data[4..19]);
+        state.finish()
+    };
+    assert_eq!(sub.checksum(), expected);
+}
+
 #[test]
 fn test_many_writes() {
     let alloc = Allocator::new(128, 10).unwrap();